@@ -3,6 +3,14 @@
 use std::time::Duration;
 use criterion::*;
 
+#[path = "../src/padding.rs"]
+mod padding;
+#[path = "../src/compression.rs"]
+mod compression;
+#[path = "../src/header.rs"]
+mod header;
+#[path = "../src/parallel.rs"]
+mod parallel;
 #[path = "../src/packer_tight.rs"]
 mod packer_tight;
 
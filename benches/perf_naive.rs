@@ -3,7 +3,15 @@
 use std::time::Duration;
 use criterion::*;
 
-#[path = "../src/packer.rs"]
+#[path = "../src/padding.rs"]
+mod padding;
+#[path = "../src/compression.rs"]
+mod compression;
+#[path = "../src/header.rs"]
+mod header;
+#[path = "../src/parallel.rs"]
+mod parallel;
+#[path = "../src/packer_naive.rs"]
 mod packer;
 
 fn benchmark_naive_packing(c: &mut Criterion) {
@@ -11,7 +19,7 @@ fn benchmark_naive_packing(c: &mut Criterion) {
     let data: Vec<u8> = (0..packer::MAX_USEFUL_BYTES_PER_TX - 5).map(|_| { rand::random::<u8>() }).collect();
 
     c.bench_function("naive_packing", |b| b.iter(|| {
-        let blobs = packer::get_blobs_from_data(&data);
+        let blobs = packer::get_blobs_from_data(&data, packer::PaddingScheme::Iso9797, false);
     }));
 }
 
@@ -3,6 +3,14 @@
 use std::time::Duration;
 use criterion::*;
 
+#[path = "../src/padding.rs"]
+mod padding;
+#[path = "../src/compression.rs"]
+mod compression;
+#[path = "../src/header.rs"]
+mod header;
+#[path = "../src/parallel.rs"]
+mod parallel;
 #[path = "../src/packer_naive.rs"]
 mod packer_naive;
 #[path = "../src/packer_tight.rs"]
@@ -14,7 +22,7 @@ fn benchmark_packing(c: &mut Criterion) {
 
     // Pack naively
     c.bench_function("naive_packing", |b| b.iter(|| {
-        let _blobs = packer_naive::get_blobs_from_data(&data);
+        let _blobs = packer_naive::get_blobs_from_data(&data, packer_naive::PaddingScheme::Iso9797, false);
     }));
 
     // Pack tightly
@@ -0,0 +1,33 @@
+/// Deflate-based (zlib) compression used to shrink a payload before it is framed into a
+/// container header, so more logical data can fit into the packers' fixed one- or two-blob
+/// budget. Compression is attempted unconditionally by the caller; it is up to them to compare
+/// sizes and only keep the compressed form when it actually helps.
+use std::io::{Read, Write};
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+/// Compress `data` with zlib at the default compression level.
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).expect("in-memory compression should not fail");
+    encoder.finish().expect("in-memory compression should not fail")
+}
+
+/// The most a zlib stream can plausibly expand by; used to cap how much we'll pre-allocate for
+/// an `original_len` that hasn't been verified against the actual decompressed size yet.
+const MAX_EXPANSION_RATIO: usize = 1032;
+
+/// Reverse [`compress`]. `original_len` is only used to pre-size the output buffer, and since it
+/// comes from the container header of a blob that may not be trustworthy yet (the checksum is
+/// only verified over the still-compressed `data`), it's capped against `data.len()` instead of
+/// being trusted outright — otherwise a tiny blob claiming a huge `original_len` could make this
+/// allocate and abort the process before `read_to_end` ever notices the stream is short.
+pub fn decompress(data: &[u8], original_len: usize) -> std::io::Result<Vec<u8>> {
+    let mut decoder = ZlibDecoder::new(data);
+    let capacity = original_len.min(data.len().saturating_mul(MAX_EXPANSION_RATIO));
+    let mut out = Vec::with_capacity(capacity);
+    decoder.read_to_end(&mut out)?;
+    Ok(out)
+}
@@ -0,0 +1,248 @@
+/// A self-describing container header that `get_blobs_from_data` prepends to the payload before
+/// it is padded and packed into field elements.
+///
+/// It carries a magic constant, a format byte recording which packer, padding scheme, and
+/// compression were used, the length of the (possibly compressed) data stored on the wire, the
+/// original uncompressed payload length, and a CRC32 checksum over the stored data. This lets the
+/// unpacking side take the precise byte count from the header, undo compression when it was
+/// applied, and catch corrupted or mismatched blobs before trying to interpret them. The packers
+/// still undo the padding (for ISO/IEC 9797-1, a backwards scan for the separator) before they
+/// parse the header.
+
+use crc32fast::Hasher;
+use thiserror::Error;
+
+use crate::compression;
+use crate::padding::PaddingScheme;
+
+pub const MAGIC: [u8; 8] = *b"BLOBBERS";
+pub const HEADER_LEN: usize = MAGIC.len() + 1 + 8 + 8 + 4;
+
+/// Which packer produced (or should consume) a framed blob.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackerKind {
+    Naive,
+    Tight,
+}
+
+#[derive(Error, Debug)]
+pub enum HeaderError {
+    #[error("Not enough data for a header")]
+    TooShort,
+    #[error("Bad magic bytes")]
+    BadMagic,
+    #[error("Unknown packer/padding/compression format byte")]
+    UnknownFormat,
+    #[error("Header says this blob was packed by a different packer")]
+    WrongPacker,
+    #[error("Payload is shorter than the length recorded in the header")]
+    TruncatedPayload,
+    #[error("Checksum mismatch")]
+    ChecksumMismatch,
+    #[error("Failed to decompress payload: {0}")]
+    DecompressionFailed(#[from] std::io::Error),
+}
+
+pub struct Header {
+    pub packer: PackerKind,
+    pub scheme: PaddingScheme,
+    pub compressed: bool,
+    /// The original, uncompressed payload length.
+    pub length: u64,
+}
+
+fn encode_format(packer: PackerKind, scheme: PaddingScheme, compressed: bool) -> u8 {
+    let packer_bit: u8 = match packer {
+        PackerKind::Naive => 0,
+        PackerKind::Tight => 1,
+    };
+    let scheme_bit: u8 = match scheme {
+        PaddingScheme::Iso9797 => 0,
+        PaddingScheme::RandomPrefix => 1,
+    };
+    let compressed_bit: u8 = if compressed { 1 } else { 0 };
+    packer_bit | (scheme_bit << 1) | (compressed_bit << 2)
+}
+
+fn decode_format(byte: u8) -> Result<(PackerKind, PaddingScheme, bool), HeaderError> {
+    if byte & !0b111 != 0 {
+        return Err(HeaderError::UnknownFormat);
+    }
+
+    let packer = match byte & 0b1 {
+        0 => PackerKind::Naive,
+        _ => PackerKind::Tight,
+    };
+    let scheme = match (byte >> 1) & 0b1 {
+        0 => PaddingScheme::Iso9797,
+        _ => PaddingScheme::RandomPrefix,
+    };
+    let compressed = (byte >> 2) & 0b1 != 0;
+
+    Ok((packer, scheme, compressed))
+}
+
+/// Prepend a header to `payload`: magic, a format byte recording `packer`, `scheme`, and whether
+/// compression was applied, the stored (on-wire) length, the original payload length, and a
+/// CRC32 checksum over the stored data.
+///
+/// `payload` is compressed with zlib and the compressed form is kept only if it is actually
+/// smaller than `payload` itself; otherwise `payload` is stored as-is and the compressed flag is
+/// left unset. Because of that comparison, `frame` needs `payload` as one contiguous, fully
+/// materialized buffer up front: it can't commit to a choice (and therefore can't start writing
+/// the stored bytes) until it has compressed the whole thing and knows both lengths.
+pub fn frame(packer: PackerKind, scheme: PaddingScheme, payload: &[u8]) -> Vec<u8> {
+    let compressed_payload = compression::compress(payload);
+    let (stored, compressed): (&[u8], bool) = if compressed_payload.len() < payload.len() {
+        (&compressed_payload, true)
+    } else {
+        (payload, false)
+    };
+
+    let mut hasher = Hasher::new();
+    hasher.update(stored);
+    let checksum = hasher.finalize();
+
+    let mut framed = Vec::with_capacity(HEADER_LEN + stored.len());
+    framed.extend_from_slice(&MAGIC);
+    framed.push(encode_format(packer, scheme, compressed));
+    framed.extend_from_slice(&(stored.len() as u64).to_le_bytes());
+    framed.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    framed.extend_from_slice(&checksum.to_le_bytes());
+    framed.extend_from_slice(stored);
+
+    framed
+}
+
+/// Parse the header at the front of `framed`, verify it was produced by `expected_packer`, and
+/// return it along with the original payload (verifying the magic and the checksum, and undoing
+/// compression, too).
+pub fn unframe(framed: &[u8], expected_packer: PackerKind) -> Result<(Header, Vec<u8>), HeaderError> {
+    if framed.len() < HEADER_LEN {
+        return Err(HeaderError::TooShort);
+    }
+    if framed[..MAGIC.len()] != MAGIC {
+        return Err(HeaderError::BadMagic);
+    }
+
+    let (packer, scheme, compressed) = decode_format(framed[MAGIC.len()])?;
+    if packer != expected_packer {
+        return Err(HeaderError::WrongPacker);
+    }
+
+    let stored_len_start = MAGIC.len() + 1;
+    let stored_len = u64::from_le_bytes(framed[stored_len_start..stored_len_start + 8].try_into().unwrap());
+    let length_start = stored_len_start + 8;
+    let length = u64::from_le_bytes(framed[length_start..length_start + 8].try_into().unwrap());
+    let checksum_start = length_start + 8;
+    let checksum = u32::from_le_bytes(framed[checksum_start..checksum_start + 4].try_into().unwrap());
+
+    let stored_end = HEADER_LEN.checked_add(stored_len as usize).ok_or(HeaderError::TruncatedPayload)?;
+    let stored = framed.get(HEADER_LEN..stored_end).ok_or(HeaderError::TruncatedPayload)?;
+
+    let mut hasher = Hasher::new();
+    hasher.update(stored);
+    if hasher.finalize() != checksum {
+        return Err(HeaderError::ChecksumMismatch);
+    }
+
+    let payload = if compressed {
+        compression::decompress(stored, length as usize)?
+    } else {
+        stored.to_vec()
+    };
+
+    Ok((Header { packer, scheme, compressed, length }, payload))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_then_unframe() {
+        let payload = b"hello blobs".to_vec();
+        let framed = frame(PackerKind::Naive, PaddingScheme::Iso9797, &payload);
+
+        let (header, recovered) = unframe(&framed, PackerKind::Naive).unwrap();
+        assert_eq!(header.packer, PackerKind::Naive);
+        assert_eq!(header.scheme, PaddingScheme::Iso9797);
+        assert_eq!(header.length, payload.len() as u64);
+        assert_eq!(recovered, payload);
+    }
+
+    #[test]
+    fn frame_compresses_when_smaller() {
+        // Highly repetitive data compresses well, so the header should keep the compressed form.
+        let payload = vec![0x42u8; 4096];
+        let framed = frame(PackerKind::Tight, PaddingScheme::Iso9797, &payload);
+        assert!(framed.len() < HEADER_LEN + payload.len());
+
+        let (header, recovered) = unframe(&framed, PackerKind::Tight).unwrap();
+        assert!(header.compressed);
+        assert_eq!(header.length, payload.len() as u64);
+        assert_eq!(recovered, payload);
+    }
+
+    #[test]
+    fn frame_skips_compression_when_it_does_not_help() {
+        // Random data has nowhere to compress to, so the raw form should be kept.
+        let payload: Vec<u8> = (0..256).map(|_| rand::random::<u8>()).collect();
+        let framed = frame(PackerKind::Naive, PaddingScheme::Iso9797, &payload);
+
+        let (header, recovered) = unframe(&framed, PackerKind::Naive).unwrap();
+        assert!(!header.compressed);
+        assert_eq!(recovered, payload);
+    }
+
+    #[test]
+    fn unframe_rejects_wrong_packer() {
+        let framed = frame(PackerKind::Tight, PaddingScheme::RandomPrefix, b"data");
+        assert!(matches!(unframe(&framed, PackerKind::Naive), Err(HeaderError::WrongPacker)));
+    }
+
+    #[test]
+    fn unframe_rejects_bad_magic() {
+        let mut framed = frame(PackerKind::Naive, PaddingScheme::Iso9797, b"data");
+        framed[0] ^= 0xff;
+        assert!(matches!(unframe(&framed, PackerKind::Naive), Err(HeaderError::BadMagic)));
+    }
+
+    #[test]
+    fn unframe_rejects_huge_stored_len_without_overflow() {
+        // Blob contents are public on-chain, so an adversary can set the stored-length field to
+        // any u64; this must be reported as truncated rather than panicking on overflow.
+        let mut framed = frame(PackerKind::Naive, PaddingScheme::Iso9797, b"data");
+        let stored_len_start = MAGIC.len() + 1;
+        framed[stored_len_start..stored_len_start + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(matches!(unframe(&framed, PackerKind::Naive), Err(HeaderError::TruncatedPayload)));
+    }
+
+    #[test]
+    fn unframe_caps_decompression_capacity_for_huge_original_length() {
+        // `length` is read straight off the header and isn't checksummed on its own (only `stored`
+        // is), so a malicious blob can set it to anything; decompression must not blindly
+        // pre-allocate that many bytes before finding out the stream doesn't actually hold them.
+        let payload = vec![0x42u8; 4096];
+        let mut framed = frame(PackerKind::Naive, PaddingScheme::Iso9797, &payload);
+        let length_start = MAGIC.len() + 1 + 8;
+        framed[length_start..length_start + 8].copy_from_slice(&(u64::MAX / 2).to_le_bytes());
+
+        let (_header, recovered) = unframe(&framed, PackerKind::Naive).unwrap();
+        assert_eq!(recovered, payload);
+    }
+
+    #[test]
+    fn unframe_rejects_too_short() {
+        let framed = vec![0u8; HEADER_LEN - 1];
+        assert!(matches!(unframe(&framed, PackerKind::Naive), Err(HeaderError::TooShort)));
+    }
+
+    #[test]
+    fn unframe_rejects_checksum_mismatch() {
+        let mut framed = frame(PackerKind::Naive, PaddingScheme::Iso9797, b"data");
+        let last = framed.len() - 1;
+        framed[last] ^= 0xff;
+        assert!(matches!(unframe(&framed, PackerKind::Naive), Err(HeaderError::ChecksumMismatch)));
+    }
+}
@@ -0,0 +1,77 @@
+pub mod padding;
+pub mod compression;
+pub mod header;
+pub mod parallel;
+pub mod packer_naive;
+pub mod packer_tight;
+
+use thiserror::Error;
+
+use crate::padding::PaddingScheme;
+
+#[derive(Error, Debug)]
+pub enum UnpackingError {
+    #[error("No blobs to unpack")]
+    NoBlobs,
+    #[error("Blob is {0} bytes; that matches neither packer_naive's nor packer_tight's blob size")]
+    UnknownBlobSize(usize),
+    #[error(transparent)]
+    Naive(#[from] packer_naive::PackingError),
+    #[error(transparent)]
+    Tight(#[from] packer_tight::PackingError),
+}
+
+/// Recover the original data from `blobs` without the caller having to already know whether
+/// [`packer_naive`] or [`packer_tight`] produced them: the two packers emit distinctly-sized
+/// blobs ([`packer_naive::BLOB_SIZE`] vs [`packer_tight::BLOB_SIZE`]), so the blob length alone is
+/// enough to pick the matching `get_data_from_blobs`, which then verifies the container header
+/// (magic, checksum, and recorded packer) as usual.
+pub fn get_data_from_blobs(blobs: &[Vec<u8>], scheme: PaddingScheme) -> Result<Vec<u8>, UnpackingError> {
+    let blob_len = blobs.first().ok_or(UnpackingError::NoBlobs)?.len();
+
+    if blob_len == packer_naive::BLOB_SIZE {
+        let mut fixed: Vec<[u8; packer_naive::BLOB_SIZE]> = Vec::with_capacity(blobs.len());
+        for b in blobs {
+            fixed.push(b.as_slice().try_into().map_err(|_| UnpackingError::UnknownBlobSize(b.len()))?);
+        }
+        Ok(packer_naive::get_data_from_blobs(&fixed, scheme)?)
+    } else if blob_len == packer_tight::BLOB_SIZE {
+        let mut fixed: Vec<[u8; packer_tight::BLOB_SIZE]> = Vec::with_capacity(blobs.len());
+        for b in blobs {
+            fixed.push(b.as_slice().try_into().map_err(|_| UnpackingError::UnknownBlobSize(b.len()))?);
+        }
+        Ok(packer_tight::get_data_from_blobs(&fixed, scheme)?)
+    } else {
+        Err(UnpackingError::UnknownBlobSize(blob_len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dispatches_to_naive() {
+        let data = b"hello from naive".to_vec();
+        let blobs = packer_naive::get_blobs_from_data(&data, PaddingScheme::Iso9797, false).unwrap();
+        let blobs: Vec<Vec<u8>> = blobs.iter().map(|b| b.to_vec()).collect();
+        assert_eq!(get_data_from_blobs(&blobs, PaddingScheme::Iso9797).unwrap(), data);
+    }
+
+    #[test]
+    fn dispatches_to_tight() {
+        let data = b"hello from tight".to_vec();
+        let blobs = packer_tight::get_blobs_from_data(&data, PaddingScheme::Iso9797, false).unwrap();
+        let blobs: Vec<Vec<u8>> = blobs.iter().map(|b| b.to_vec()).collect();
+        assert_eq!(get_data_from_blobs(&blobs, PaddingScheme::Iso9797).unwrap(), data);
+    }
+
+    #[test]
+    fn rejects_empty_and_unknown_blob_sizes() {
+        assert!(matches!(get_data_from_blobs(&[], PaddingScheme::Iso9797), Err(UnpackingError::NoBlobs)));
+        assert!(matches!(
+            get_data_from_blobs(&[vec![0u8; 3]], PaddingScheme::Iso9797),
+            Err(UnpackingError::UnknownBlobSize(3))
+        ));
+    }
+}
@@ -0,0 +1,263 @@
+/// A naive packer that packs 31bytes (248 bits) per field element.
+
+use thiserror::Error;
+use bytes::{Buf, Bytes, BytesMut};
+
+use crate::header::{self, PackerKind};
+use crate::padding;
+use crate::parallel;
+pub use crate::padding::{Padding, PaddingScheme};
+
+/// The number of field elements per blob
+const FIELD_ELEMENTS_PER_BLOB: usize = 1016;
+/// Max number of blobs per transaction
+const MAX_BLOBS_PER_TX: usize = 2;
+/// Bytes per field element (including useless part of field element)
+const BYTES_PER_FIELD_ELEMENT: usize = 32;
+/// Number of useful bytes we can fit into a field element (the rest need to be zero to fit into the modulus)
+const USEFUL_BYTES_PER_FIELD_ELEMENT: usize = 31;
+
+/// The number of useful bytes of data we can fit into one blob
+const USEFUL_BYTES_PER_BLOB: usize = USEFUL_BYTES_PER_FIELD_ELEMENT * FIELD_ELEMENTS_PER_BLOB;
+/// The max amount of useful bytes we can fit into one transaction: one byte is reserved for the
+/// padding separator, and `header::HEADER_LEN` bytes are reserved for the container header that
+/// `get_blobs_from_data` prepends before padding.
+pub const MAX_USEFUL_BYTES_PER_TX: usize = (USEFUL_BYTES_PER_BLOB * MAX_BLOBS_PER_TX) - 1 - header::HEADER_LEN;
+/// The actual size of a blob (including the useless part of field elements). Distinct from
+/// [`crate::packer_tight::BLOB_SIZE`], which lets callers that don't know in advance which packer
+/// produced a blob tell them apart just by length; see [`crate::get_data_from_blobs`].
+pub const BLOB_SIZE: usize = BYTES_PER_FIELD_ELEMENT * FIELD_ELEMENTS_PER_BLOB;
+
+/// A blob (just a bunch of bytes really...)
+type Blob = [u8; BLOB_SIZE];
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Error, Debug)]
+pub enum PackingError {
+    #[error("Bad data length")]
+    DataLengthError,
+    #[error("Failed to unpad")]
+    UnpadError,
+    #[error("Bad container header: {0}")]
+    HeaderError(#[from] header::HeaderError),
+}
+
+impl From<padding::PaddingError> for PackingError {
+    fn from(_: padding::PaddingError) -> Self {
+        PackingError::UnpadError
+    }
+}
+
+/// Strip the forced-zero byte out of every field element
+fn clean_field_elements(data: &mut Vec<u8>) {
+    let mut index = 0;
+    data.retain(|_| {
+        index += 1;
+        index % 32 != 0
+    });
+}
+
+/// Build and return a blob from arbitrary data
+fn get_blob(data: &[u8; USEFUL_BYTES_PER_BLOB]) -> Blob {
+    let mut blob = [0; BLOB_SIZE];
+
+    // Start packing!  Data needs to be encoded as valid field elements to be a blob.
+    for i in 0..FIELD_ELEMENTS_PER_BLOB {
+        // Each field element is 32 bytes long, but only the first 31 bytes are used for actual data
+        let mut chunk = vec![0; 32];
+        // Copy data into the first 31 bytes
+        chunk[..31].clone_from_slice(&data[i*31..(i+1)*31]);
+        // Copy the entire 32 bytes into the blob
+        blob[i*32..(i+1)*32].clone_from_slice(&chunk);
+    }
+
+//    println!("[*] New blob: {:?}", blob);
+    return blob
+}
+
+/// Like [`get_blob`], but pulls its input straight out of a [`Buf`] (via `copy_to_slice`, which
+/// advances the cursor one chunk at a time) instead of requiring a pre-materialized array, and
+/// returns the blob as a cheaply-cloneable [`Bytes`] instead of a fixed-size array.
+fn get_blob_from_buf(data: &mut impl Buf) -> Bytes {
+    let mut blob = BytesMut::zeroed(BLOB_SIZE);
+
+    for i in 0..FIELD_ELEMENTS_PER_BLOB {
+        // Each field element is 32 bytes long, but only the first 31 bytes are used for actual
+        // data; the last byte of the slot is left zero.
+        data.copy_to_slice(&mut blob[i*32..i*32+31]);
+    }
+
+    blob.freeze()
+}
+
+/// Given the data in an array, frame it with a container header, pad it using `scheme`, and
+/// return a list of blobs. If `parallel` is set, blobs are packed across a pool of worker
+/// threads instead of one at a time; see [`crate::parallel`].
+pub fn get_blobs_from_data(data: &[u8], scheme: PaddingScheme, parallel: bool) -> Result<Vec<Blob>, PackingError> {
+    if data.len() == 0 {
+        println!("[!] Got no data as input. Exiting without doing any work.");
+        return Err(PackingError::DataLengthError);
+    }
+
+    let framed_data = header::frame(PackerKind::Naive, scheme, data);
+    let padded_data = scheme.padding(USEFUL_BYTES_PER_BLOB).pad(&framed_data, USEFUL_BYTES_PER_BLOB);
+    let n_blobs_needed = padded_data.len() / USEFUL_BYTES_PER_BLOB;
+//    println!("[*] We got {} bytes, we will need {} blobs for that!", data.len(), n_blobs_needed);
+
+    if n_blobs_needed > MAX_BLOBS_PER_TX {
+        println!("[!] You provided {} bytes, but we can only pack {} bytes into a single tx. Aborting!", data.len(), MAX_USEFUL_BYTES_PER_TX);
+        return Err(PackingError::DataLengthError);
+    }
+//    println!("[*] We started with {} bytes; now we have {} padded bytes [{:?}]!", data.len(), padded_data.len(), padded_data);
+
+    let blobs = if parallel {
+        parallel::pack_chunks_parallel(&padded_data, USEFUL_BYTES_PER_BLOB, n_blobs_needed, |chunk| {
+            get_blob(chunk.try_into().expect("bad chunking"))
+        })
+    } else {
+        let mut blobs = Vec::<Blob>::with_capacity(n_blobs_needed);
+        for i in 0..n_blobs_needed {
+            // Get a bunch of data, and pack it into a blob
+            let chunk = &padded_data[i*USEFUL_BYTES_PER_BLOB..(i+1)*USEFUL_BYTES_PER_BLOB];
+            let blob = get_blob(chunk.try_into().expect("bad chunking"));
+//        println!("[*] Got {}th blob: {} bytes", i, blob.len());
+            blobs.push(blob)
+        }
+        blobs
+    };
+
+    return Ok(blobs);
+}
+
+/// Like [`get_blobs_from_data`], but accepts anything implementing [`Buf`] (for example a chain
+/// of non-contiguous `Bytes` segments) instead of a single `&[u8]`, and returns the packed blobs
+/// as cheaply-cloneable [`Bytes`] instead of fixed-size arrays, so downstream transaction
+/// builders can slice and clone them without copying.
+///
+/// This is only a win on the output side. `data` is still drained into one `Vec<u8>` up front, at
+/// the same cost as `get_blobs_from_data` taking a `&[u8]`: [`header::frame`] has to compress the
+/// whole payload and compare it against the uncompressed length before it can decide which one to
+/// store, so it needs the payload contiguous and fully known regardless of how it got here.
+/// Avoiding that would need a different on-wire format (e.g. one that doesn't pick between
+/// compressed/raw after the fact), not just a different entry point. It's only the per-blob field
+/// element filling below that streams straight out of a `Buf` instead of indexing a slice.
+pub fn get_blobs_from_buf(mut data: impl Buf, scheme: PaddingScheme) -> Result<Vec<Bytes>, PackingError> {
+    if !data.has_remaining() {
+        println!("[!] Got no data as input. Exiting without doing any work.");
+        return Err(PackingError::DataLengthError);
+    }
+
+    let mut owned_data = Vec::with_capacity(data.remaining());
+    while data.has_remaining() {
+        let chunk_len = data.chunk().len();
+        owned_data.extend_from_slice(data.chunk());
+        data.advance(chunk_len);
+    }
+
+    let framed_data = header::frame(PackerKind::Naive, scheme, &owned_data);
+    let padded_data = scheme.padding(USEFUL_BYTES_PER_BLOB).pad(&framed_data, USEFUL_BYTES_PER_BLOB);
+    let n_blobs_needed = padded_data.len() / USEFUL_BYTES_PER_BLOB;
+
+    if n_blobs_needed > MAX_BLOBS_PER_TX {
+        println!("[!] You provided {} bytes, but we can only pack {} bytes into a single tx. Aborting!", owned_data.len(), MAX_USEFUL_BYTES_PER_TX);
+        return Err(PackingError::DataLengthError);
+    }
+
+    let mut padded_buf = Bytes::from(padded_data);
+    let mut blobs = Vec::with_capacity(n_blobs_needed);
+    for _ in 0..n_blobs_needed {
+        blobs.push(get_blob_from_buf(&mut padded_buf));
+    }
+
+    Ok(blobs)
+}
+
+/// Given a list of blobs produced by [`get_blobs_from_data`] with the same `scheme`, recover the
+/// original data: strip the forced-zero byte out of every field element, undo the padding, then
+/// parse and verify the container header to recover the exact payload.
+pub fn get_data_from_blobs(blobs: &[Blob], scheme: PaddingScheme) -> Result<Vec<u8>, PackingError> {
+    let mut data = blobs.concat();
+    clean_field_elements(&mut data);
+    let framed_data = scheme.padding(USEFUL_BYTES_PER_BLOB).unpad(&data)?;
+    let (_header, payload) = header::unframe(&framed_data, PackerKind::Naive)?;
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pack() {
+        let data: Vec<u8> = (0..USEFUL_BYTES_PER_BLOB + 5).map(|_| { rand::random::<u8>() }).collect();
+        let blobs = get_blobs_from_data(&data, PaddingScheme::Iso9797, false).unwrap();
+
+        assert_eq!(blobs.concat().len(), blobs.len() * BLOB_SIZE);
+
+        let rcved_data = get_data_from_blobs(&blobs, PaddingScheme::Iso9797).unwrap();
+        assert_eq!(rcved_data, data)
+    }
+
+    #[test]
+    fn pack_at_max_tx_capacity() {
+        // Regression test: MAX_USEFUL_BYTES_PER_TX is documented as the max payload a transaction
+        // can hold, so it must actually round-trip once the container header is accounted for.
+        let data: Vec<u8> = (0..MAX_USEFUL_BYTES_PER_TX).map(|_| { rand::random::<u8>() }).collect();
+        let blobs = get_blobs_from_data(&data, PaddingScheme::Iso9797, false).unwrap();
+
+        let rcved_data = get_data_from_blobs(&blobs, PaddingScheme::Iso9797).unwrap();
+        assert_eq!(rcved_data, data)
+    }
+
+    #[test]
+    fn pack_exact_capacity() {
+        // Regression test: data that exactly fills every useful byte of the blobs it needs must
+        // still round-trip (there used to be no room left for the 0x80 separator). The container
+        // header is part of what gets padded, so leave room for it.
+        let data: Vec<u8> = (0..USEFUL_BYTES_PER_BLOB - header::HEADER_LEN).map(|_| { rand::random::<u8>() }).collect();
+        let blobs = get_blobs_from_data(&data, PaddingScheme::Iso9797, false).unwrap();
+
+        let rcved_data = get_data_from_blobs(&blobs, PaddingScheme::Iso9797).unwrap();
+        assert_eq!(rcved_data, data)
+    }
+
+    #[test]
+    fn pack_random_prefix() {
+        // RandomPrefixPadding always reserves at least one full block for its prefix and trailer,
+        // so keep the data small enough to stay within MAX_BLOBS_PER_TX.
+        let data: Vec<u8> = (0..100).map(|_| { rand::random::<u8>() }).collect();
+        let blobs = get_blobs_from_data(&data, PaddingScheme::RandomPrefix, false).unwrap();
+
+        let rcved_data = get_data_from_blobs(&blobs, PaddingScheme::RandomPrefix).unwrap();
+        assert_eq!(rcved_data, data)
+    }
+
+    #[test]
+    fn pack_parallel_matches_sequential() {
+        let data: Vec<u8> = (0..USEFUL_BYTES_PER_BLOB + 5).map(|_| { rand::random::<u8>() }).collect();
+        let blobs = get_blobs_from_data(&data, PaddingScheme::Iso9797, true).unwrap();
+        let sequential = get_blobs_from_data(&data, PaddingScheme::Iso9797, false).unwrap();
+        assert_eq!(blobs.concat(), sequential.concat());
+
+        let rcved_data = get_data_from_blobs(&blobs, PaddingScheme::Iso9797).unwrap();
+        assert_eq!(rcved_data, data)
+    }
+
+    #[test]
+    fn pack_from_buf_matches_pack_from_slice() {
+        let data: Vec<u8> = (0..USEFUL_BYTES_PER_BLOB + 5).map(|_| { rand::random::<u8>() }).collect();
+
+        // Feed the buf-based entry point a chain of non-contiguous segments.
+        let (first, second) = data.split_at(data.len() / 3);
+        let fragmented = bytes::Bytes::copy_from_slice(first).chain(bytes::Bytes::copy_from_slice(second));
+
+        let blobs = get_blobs_from_buf(fragmented, PaddingScheme::Iso9797).unwrap();
+        let blobs: Vec<Blob> = blobs.iter().map(|b| b.as_ref().try_into().unwrap()).collect();
+        let from_slice = get_blobs_from_data(&data, PaddingScheme::Iso9797, false).unwrap();
+        assert_eq!(blobs.concat(), from_slice.concat());
+
+        let rcved_data = get_data_from_blobs(&blobs, PaddingScheme::Iso9797).unwrap();
+        assert_eq!(rcved_data, data)
+    }
+}
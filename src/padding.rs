@@ -0,0 +1,224 @@
+/// Padding schemes used to fill data out to a multiple of a packer's block size before it is
+/// split into field elements.
+///
+/// Blob contents are public on-chain, so a scheme that always pads with deterministic trailing
+/// zeros (like ISO/IEC 9797-1) leaks the exact payload length and makes identical payloads
+/// recognizable. [`RandomPrefixPadding`] avoids that by hiding the payload behind a randomized
+/// prefix instead.
+
+use rand::Rng;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum PaddingError {
+    #[error("Failed to unpad")]
+    UnpadError,
+}
+
+/// A padding scheme: pads `data` out to a multiple of `block_len` bytes, and reverses that to
+/// recover the original data.
+pub trait Padding {
+    /// Pad `data` so that the result is the smallest multiple of `block_len` bytes that can fit
+    /// it (plus whatever bookkeeping the scheme needs).
+    fn pad(&self, data: &[u8], block_len: usize) -> Vec<u8>;
+
+    /// Recover the original data from a buffer produced by `pad`.
+    fn unpad(&self, data: &[u8]) -> Result<Vec<u8>, PaddingError>;
+}
+
+/// The classic ISO/IEC 9797-1 padding method 2: append a single `0x80` byte, then zeros up to the
+/// block boundary. Unpadding scans backwards for the `0x80` separator.
+pub struct Iso9797Padding;
+
+impl Padding for Iso9797Padding {
+    fn pad(&self, data: &[u8], block_len: usize) -> Vec<u8> {
+        let n_blocks = (data.len() + 1).div_ceil(block_len); // +1: room for the 0x80 separator
+        let mut padded = vec![0; n_blocks * block_len];
+
+        padded[..data.len()].clone_from_slice(data);
+        padded[data.len()] = 0x80;
+
+        padded
+    }
+
+    fn unpad(&self, data: &[u8]) -> Result<Vec<u8>, PaddingError> {
+        for i in (0..data.len()).rev() {
+            match data[i] {
+                0x80 => return Ok(data[..i].to_vec()),
+                0x00 => continue,
+                _ => return Err(PaddingError::UnpadError),
+            }
+        }
+        Err(PaddingError::UnpadError)
+    }
+}
+
+/// A randomized padding scheme that hides the payload length instead of leaking it through
+/// trailing zeros. For a message of `size` bytes and block size `N` it computes
+/// `pad_len = (-size - 2) mod N + 2`, prepends `pad_len + 1` bytes (a length field encoding
+/// `pad_len - 2` followed by random filler) and appends `N - 1` trailing `\0` bytes.
+///
+/// `N` needs to be remembered to unpad, since the trailing filler carries no information of its
+/// own; we stash it in the struct at construction time rather than threading it back through
+/// `unpad`.
+pub struct RandomPrefixPadding {
+    block_len: usize,
+}
+
+impl RandomPrefixPadding {
+    pub fn new(block_len: usize) -> Self {
+        Self { block_len }
+    }
+
+    /// Number of bits needed to represent any value in `0..n`.
+    fn bits_needed(n: usize) -> u32 {
+        if n <= 1 {
+            0
+        } else {
+            usize::BITS - (n - 1).leading_zeros()
+        }
+    }
+
+    /// Number of bytes at the front of the prefix whose low bits carry the length field.
+    fn len_field_bytes(&self) -> usize {
+        (Self::bits_needed(self.block_len) as usize).div_ceil(8).max(1)
+    }
+}
+
+impl Padding for RandomPrefixPadding {
+    fn pad(&self, data: &[u8], block_len: usize) -> Vec<u8> {
+        // The trait takes `block_len` so `Iso9797Padding` can stay stateless, but this scheme's
+        // length-field bit-width is fixed at construction time (see `len_field_bytes`); `unpad`
+        // only ever has `self.block_len` to work with, so the two must agree.
+        debug_assert_eq!(block_len, self.block_len, "block_len must match the value passed to RandomPrefixPadding::new");
+
+        let size = data.len() as i128;
+        let n = self.block_len as i128;
+        let pad_len = (-size - 2).rem_euclid(n) as usize + 2;
+
+        let mut prefix = vec![0u8; pad_len + 1];
+        rand::thread_rng().fill(&mut prefix[..]);
+
+        // Stamp `pad_len - 2` into the low bits of the length field, leaving every other bit
+        // (including the unused high bits of the length field itself) random.
+        let value = (pad_len - 2) as u128;
+        let bits = Self::bits_needed(self.block_len);
+        for (i, byte) in prefix.iter_mut().take(self.len_field_bytes()).enumerate() {
+            let byte_bits = bits.saturating_sub(8 * i as u32).min(8);
+            let mask = if byte_bits >= 8 { 0xffu8 } else { (1u8 << byte_bits) - 1 };
+            let encoded = ((value >> (8 * i)) & 0xff) as u8 & mask;
+            *byte = (*byte & !mask) | encoded;
+        }
+
+        let mut padded = Vec::with_capacity(prefix.len() + data.len() + self.block_len - 1);
+        padded.extend_from_slice(&prefix);
+        padded.extend_from_slice(data);
+        padded.extend(std::iter::repeat_n(0u8, self.block_len - 1));
+
+        padded
+    }
+
+    fn unpad(&self, data: &[u8]) -> Result<Vec<u8>, PaddingError> {
+        let len_field_bytes = self.len_field_bytes();
+        if data.len() < len_field_bytes {
+            return Err(PaddingError::UnpadError);
+        }
+
+        let bits = Self::bits_needed(self.block_len);
+        let mut value: u128 = 0;
+        for (i, byte) in data.iter().take(len_field_bytes).enumerate() {
+            let byte_bits = bits.saturating_sub(8 * i as u32).min(8);
+            let mask = if byte_bits >= 8 { 0xffu8 } else { (1u8 << byte_bits) - 1 };
+            value |= ((byte & mask) as u128) << (8 * i);
+        }
+
+        let pad_len = value as usize + 2;
+        let prefix_len = pad_len + 1;
+        let trailing_len = self.block_len - 1;
+
+        let message_len = data
+            .len()
+            .checked_sub(prefix_len)
+            .and_then(|n| n.checked_sub(trailing_len))
+            .ok_or(PaddingError::UnpadError)?;
+
+        Ok(data[prefix_len..prefix_len + message_len].to_vec())
+    }
+}
+
+/// The padding schemes a packer can be asked to use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaddingScheme {
+    /// ISO/IEC 9797-1 padding method 2 (`0x80` then zeros).
+    Iso9797,
+    /// A randomized prefix that hides the payload length.
+    RandomPrefix,
+}
+
+impl PaddingScheme {
+    /// Build the `Padding` implementation for this scheme, for a packer whose block size is
+    /// `block_len` bytes.
+    pub fn padding(&self, block_len: usize) -> Box<dyn Padding> {
+        match self {
+            PaddingScheme::Iso9797 => Box::new(Iso9797Padding),
+            PaddingScheme::RandomPrefix => Box::new(RandomPrefixPadding::new(block_len)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(padding: &dyn Padding, block_len: usize, data: &[u8]) {
+        let padded = padding.pad(data, block_len);
+        assert_eq!(padded.len() % block_len, 0);
+        assert_eq!(padding.unpad(&padded).unwrap(), data);
+    }
+
+    #[test]
+    fn iso9797_round_trips_empty_data() {
+        round_trip(&Iso9797Padding, 16, b"");
+    }
+
+    #[test]
+    fn iso9797_round_trips_data_adjacent_to_block_len() {
+        for len in [15, 16, 17, 31, 32, 33] {
+            let data: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            round_trip(&Iso9797Padding, 16, &data);
+        }
+    }
+
+    #[test]
+    fn iso9797_unpad_rejects_garbage() {
+        assert!(matches!(Iso9797Padding.unpad(&[0x01, 0x02]), Err(PaddingError::UnpadError)));
+    }
+
+    #[test]
+    fn random_prefix_round_trips_empty_data() {
+        round_trip(&RandomPrefixPadding::new(16), 16, b"");
+    }
+
+    #[test]
+    fn random_prefix_round_trips_data_adjacent_to_block_len() {
+        for len in [15, 16, 17, 31, 32, 33] {
+            let data: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            round_trip(&RandomPrefixPadding::new(16), 16, &data);
+        }
+    }
+
+    #[test]
+    fn random_prefix_round_trips_minimal_and_maximal_pad_len() {
+        // block_len == 1 forces the smallest possible pad_len (bits_needed(1) == 0, one length byte,
+        // all its bits unused); a larger block_len pushes pad_len up near its maximum for that size.
+        round_trip(&RandomPrefixPadding::new(1), 1, b"minimal pad");
+        round_trip(&RandomPrefixPadding::new(256), 256, b"");
+    }
+
+    #[test]
+    fn random_prefix_unpad_rejects_truncated_data() {
+        let padding = RandomPrefixPadding::new(16);
+        let padded = padding.pad(b"hello", 16);
+        assert!(matches!(padding.unpad(&padded[..1]), Err(PaddingError::UnpadError)));
+    }
+}
@@ -0,0 +1,60 @@
+/// Shared chunk-and-dispatch logic for the packers' parallel path: split padded data into
+/// per-blob chunks, shuffle the distribution order so no single worker thread gets stuck packing
+/// a run of low-entropy blobs, hand the chunks to a pool of worker threads, and reassemble the
+/// results in original blob order.
+use rand::seq::SliceRandom;
+
+/// Pack `n_blobs` chunks of `chunk_len` bytes each, taken in order from `padded_data`, into blobs
+/// using `pack_chunk`. Work is spread across worker threads (one per available core, capped at
+/// `n_blobs`); each worker is handed a shuffled share of the blob indices up front and returns its
+/// results tagged with their original indices, which the calling thread then places into their
+/// slots, so the final `Vec<B>` still comes back in original blob order.
+pub fn pack_chunks_parallel<B, F>(padded_data: &[u8], chunk_len: usize, n_blobs: usize, pack_chunk: F) -> Vec<B>
+where
+    B: Send,
+    F: Fn(&[u8]) -> B + Sync,
+{
+    let mut order: Vec<usize> = (0..n_blobs).collect();
+    order.shuffle(&mut rand::thread_rng());
+
+    let n_workers = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(n_blobs.max(1));
+    let shares = split_into_shares(&order, n_workers);
+
+    let mut blobs: Vec<Option<B>> = (0..n_blobs).map(|_| None).collect();
+    std::thread::scope(|scope| {
+        let pack_chunk = &pack_chunk;
+        let handles: Vec<_> = shares
+            .iter()
+            .map(|share| {
+                scope.spawn(move || {
+                    share
+                        .iter()
+                        .map(|&i| (i, pack_chunk(&padded_data[i * chunk_len..(i + 1) * chunk_len])))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            for (i, blob) in handle.join().expect("packing worker thread panicked") {
+                blobs[i] = Some(blob);
+            }
+        }
+    });
+
+    blobs.into_iter().map(|b| b.expect("every blob index should have been packed")).collect()
+}
+
+/// Split `order` into `n_workers` roughly-equal shares, dealt round-robin so each worker's share
+/// is spread across the shuffled order rather than a contiguous run of it.
+fn split_into_shares(order: &[usize], n_workers: usize) -> Vec<Vec<usize>> {
+    let n_workers = n_workers.max(1);
+    let mut shares = vec![Vec::new(); n_workers];
+    for (i, &idx) in order.iter().enumerate() {
+        shares[i % n_workers].push(idx);
+    }
+    shares
+}